@@ -3,13 +3,382 @@
  * Core library for TreasuryManager
  */
 
-use log::{info, error, debug};
+use log::{info, debug};
 use serde::{Serialize, Deserialize};
 use std::fs;
-use std::path::Path;
+use thiserror::Error;
 
-/// Custom result type for the library, wrapping a boxed error
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+/// Errors produced by the library, so downstream embedders can match on failure kind
+#[derive(Debug, Error)]
+pub enum TreasuryError {
+    /// An underlying filesystem or logging I/O operation failed
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    /// Input could not be parsed into the requested shape
+    #[error("parse error: {0}")]
+    Parse(String),
+    /// A value could not be serialized for output
+    #[error("serialization error: {0}")]
+    Serialize(#[from] serde_json::Error),
+    /// The global logger could not be installed
+    #[error("logger initialization error: {0}")]
+    LoggerInit(#[from] log::SetLoggerError),
+}
+
+/// Custom result type for the library
+pub type Result<T> = std::result::Result<T, TreasuryError>;
+
+/// User-configurable defaults, loaded from a TOML file
+///
+/// Discovered via an explicit `--config` path, or else an XDG-style path
+/// (`$XDG_CONFIG_HOME/treasurymanager/config.toml`, falling back to
+/// `$HOME/.config/treasurymanager/config.toml`). CLI flags always take
+/// precedence over values supplied here.
+#[derive(Debug, Default, Deserialize)]
+pub struct Configuration {
+    /// Default logging verbosity, e.g. "info" or "debug"
+    #[serde(default)]
+    pub level: Option<String>,
+    /// Default input format
+    #[serde(default)]
+    pub format: Option<InputFormat>,
+    /// Default output destination
+    #[serde(default)]
+    pub output: Option<String>,
+}
+
+impl Configuration {
+    /// Load configuration from `explicit_path`, or discover one via the
+    /// XDG-style config directory; falls back to defaults if none is found
+    pub fn load(explicit_path: Option<&str>) -> Result<Self> {
+        let path = match explicit_path {
+            Some(path) => Some(std::path::PathBuf::from(path)),
+            None => Self::discover_path(),
+        };
+
+        match path {
+            Some(path) if path.exists() => {
+                info!("Loading configuration from: {}", path.display());
+                let raw = fs::read_to_string(path)?;
+                toml::from_str(&raw).map_err(|e| TreasuryError::Parse(e.to_string()))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// Resolve the XDG-style configuration file path
+    fn discover_path() -> Option<std::path::PathBuf> {
+        let config_home = std::env::var_os("XDG_CONFIG_HOME")
+            .map(std::path::PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| std::path::PathBuf::from(home).join(".config")))?;
+        Some(config_home.join("treasurymanager").join("config.toml"))
+    }
+}
+
+/// Top-level operation mode selected via the CLI subcommand
+pub enum Mode {
+    /// Process treasury input once and persist the results as a compiled artifact
+    Compile {
+        /// Resolved logging verbosity
+        level: log::LevelFilter,
+        /// Optional path to a rotating log file
+        log_file: Option<String>,
+        /// Path to the plaintext input file
+        input: Option<String>,
+        /// Path to write the compiled artifact to
+        output: String,
+        /// Format the input file is encoded in
+        format: InputFormat,
+    },
+    /// Process input, accepting either plaintext or a previously compiled artifact
+    Run {
+        /// Resolved logging verbosity
+        level: log::LevelFilter,
+        /// Optional path to a rotating log file
+        log_file: Option<String>,
+        /// Path to the input file (plaintext or a compiled artifact)
+        input: Option<String>,
+        /// Path to output file
+        output: Option<String>,
+        /// Format the input file is encoded in
+        format: InputFormat,
+    },
+}
+
+/// Input encoding selected via `-f`/`--format`, mirrored from the CLI's arg-enum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// One opaque record per line
+    #[default]
+    Plain,
+    /// Newline-delimited JSON, one value per line
+    Ndjson,
+    /// Comma-separated values mapped onto named treasury fields
+    Csv,
+}
+
+/// A single input record, as produced by an [`InputReader`]
+#[derive(Debug)]
+pub enum Record {
+    /// A raw plaintext line
+    Line(String),
+    /// A parsed NDJSON row mapped onto named treasury fields
+    Json(JsonRecord),
+    /// A parsed CSV row mapped onto named treasury fields
+    Csv(CsvRecord),
+}
+
+/// A single NDJSON-sourced treasury record
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonRecord {
+    /// Account identifier or name
+    pub account: String,
+    /// Transaction amount, as written in the source file
+    pub amount: String,
+    /// Transaction date, as written in the source file
+    pub date: String,
+    /// Free-text description of the transaction
+    #[serde(deserialize_with = "deserialize_decoded_lossy_string")]
+    pub description: LossyString,
+}
+
+/// A single CSV-sourced treasury record
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CsvRecord {
+    /// Account identifier or name
+    pub account: String,
+    /// Transaction amount, as written in the source file
+    pub amount: String,
+    /// Transaction date, as written in the source file
+    pub date: String,
+    /// Free-text description of the transaction
+    ///
+    /// CSV fields are already-decoded plain text with no JSON `\uXXXX`
+    /// escape syntax, so this is wrapped without sanitizing it.
+    #[serde(deserialize_with = "deserialize_decoded_lossy_string")]
+    pub description: LossyString,
+}
+
+/// A `String` wrapper for free-text fields that may contain lone UTF-16 surrogates
+///
+/// Real-world exported ledgers sometimes contain free-text fields with an
+/// unpaired `\uD800`-`\uDFFF` escape, which `serde_json` otherwise rejects
+/// outright. [`LossyString::sanitize`] scans `\uXXXX` escapes, combining a
+/// valid high/low surrogate pair into its intended code point, and replacing
+/// any lone surrogate with U+FFFD instead of erroring.
+///
+/// `LossyString` has no `Deserialize` impl of its own: whether a field needs
+/// sanitizing depends on whether its source format has already decoded
+/// `\uXXXX` escapes by the time serde sees it, so every field must pick a
+/// `deserialize_with` explicitly rather than getting one for free. Structured
+/// record fields use [`deserialize_decoded_lossy_string`]; raw NDJSON lines
+/// are sanitized up front instead, before the line is even valid JSON — see
+/// [`NdjsonReader`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[serde(transparent)]
+pub struct LossyString(pub String);
+
+impl LossyString {
+    /// Scan `raw` for `\uXXXX` escapes, combining valid surrogate pairs and
+    /// replacing lone surrogates with U+FFFD
+    ///
+    /// Returns the sanitized text along with the number of lone surrogates
+    /// that were replaced.
+    fn sanitize(raw: &str) -> (String, usize) {
+        let chars: Vec<char> = raw.chars().collect();
+        let mut out = String::with_capacity(raw.len());
+        let mut replacements = 0;
+        let mut i = 0;
+
+        while i < chars.len() {
+            if let Some(high) = parse_unicode_escape(&chars, i) {
+                if is_high_surrogate(high) {
+                    if let Some(low) = parse_unicode_escape(&chars, i + 6) {
+                        if is_low_surrogate(low) {
+                            out.push(combine_surrogate_pair(high, low));
+                            i += 12;
+                            continue;
+                        }
+                    }
+                    out.push('\u{FFFD}');
+                    replacements += 1;
+                    i += 6;
+                    continue;
+                } else if is_low_surrogate(high) {
+                    out.push('\u{FFFD}');
+                    replacements += 1;
+                    i += 6;
+                    continue;
+                } else {
+                    // Any other `\uXXXX` escape (e.g. `\u0022` for a
+                    // quote, `\u000A` for a newline) is left exactly as
+                    // written. Decoding it here would reintroduce a
+                    // JSON-significant or control character into text
+                    // that still has to round-trip through
+                    // `serde_json::from_str` on the NDJSON fallback path,
+                    // turning otherwise-valid input into a parse error.
+                    for &ch in &chars[i..i + 6] {
+                        out.push(ch);
+                    }
+                    i += 6;
+                    continue;
+                }
+            }
+            if chars[i] == '\\' && chars.get(i + 1).is_some() {
+                // Any other two-character escape (`\\`, `\"`, `\/`, `\b`, `\f`,
+                // `\n`, `\r`, `\t`) is copied verbatim so its second character
+                // is never mistaken for the start of a fresh `\u` escape, e.g.
+                // an escaped backslash followed by the literal text `uD800`.
+                out.push(chars[i]);
+                out.push(chars[i + 1]);
+                i += 2;
+                continue;
+            }
+            out.push(chars[i]);
+            i += 1;
+        }
+
+        (out, replacements)
+    }
+}
+
+/// Parse a `\uXXXX` escape starting at `chars[i]`, returning the raw code unit
+fn parse_unicode_escape(chars: &[char], i: usize) -> Option<u32> {
+    if chars.get(i) != Some(&'\\') || chars.get(i + 1) != Some(&'u') {
+        return None;
+    }
+    let hex: String = chars.get(i + 2..i + 6)?.iter().collect();
+    u32::from_str_radix(&hex, 16).ok()
+}
+
+/// Whether `code` is a UTF-16 high (leading) surrogate
+fn is_high_surrogate(code: u32) -> bool {
+    (0xD800..=0xDBFF).contains(&code)
+}
+
+/// Whether `code` is a UTF-16 low (trailing) surrogate
+fn is_low_surrogate(code: u32) -> bool {
+    (0xDC00..=0xDFFF).contains(&code)
+}
+
+/// Combine a valid high/low surrogate pair into its intended code point
+fn combine_surrogate_pair(high: u32, low: u32) -> char {
+    let code = 0x10000 + (high - 0xD800) * 0x400 + (low - 0xDC00);
+    char::from_u32(code).unwrap_or('\u{FFFD}')
+}
+
+/// Wrap an already-decoded string in a [`LossyString`] without sanitizing it
+///
+/// Used for fields whose source format has no `\uXXXX` escape syntax of its
+/// own to mistakenly re-interpret (CSV), or has already resolved it before
+/// this deserializer ever runs (NDJSON, tolerated up front on the raw line
+/// instead — see [`NdjsonReader`]). Sanitizing here would instead risk
+/// mistaking literal data (e.g. a decoded `\` followed by an unrelated
+/// `uXXXX`) for an escape.
+fn deserialize_decoded_lossy_string<'de, D>(deserializer: D) -> std::result::Result<LossyString, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(LossyString(String::deserialize(deserializer)?))
+}
+
+/// Turns raw input bytes into an iterator of typed records
+///
+/// Implementations correspond to the [`InputFormat`] variants and are
+/// selected by the CLI's `-f`/`--format` flag before records ever reach
+/// [`TreasuryManagerProcessor::process`].
+pub trait InputReader {
+    /// Parse `data` into a sequence of per-record outcomes, along with the
+    /// number of lone-surrogate replacements made while reading. A record
+    /// that fails to parse becomes `Err` rather than aborting the rest of
+    /// the batch.
+    fn read(&self, data: &str) -> Result<(Vec<Result<Record>>, usize)>;
+}
+
+/// Reads plaintext input, treating each line as an opaque record
+struct PlainReader;
+
+impl InputReader for PlainReader {
+    fn read(&self, data: &str) -> Result<(Vec<Result<Record>>, usize)> {
+        Ok((data.lines().map(|line| Ok(Record::Line(line.to_string()))).collect(), 0))
+    }
+}
+
+/// Reads newline-delimited JSON, deserializing each line into a [`JsonRecord`]
+struct NdjsonReader;
+
+impl InputReader for NdjsonReader {
+    fn read(&self, data: &str) -> Result<(Vec<Result<Record>>, usize)> {
+        let mut total_replacements = 0;
+
+        // A line that fails to parse even after sanitizing becomes a single
+        // `Err` entry rather than failing the whole batch, mirroring how a
+        // record that fails once parsed is turned into one `success: false`
+        // result further down in `process_input_data`.
+        let records = data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                // Lines parse as-is in the common case, leaving valid content
+                // (including real surrogate pairs) untouched. Only a line
+                // `serde_json` itself rejects, typically on a lone surrogate
+                // it won't decode, falls back to the tolerant sanitizer.
+                let record = match serde_json::from_str(line) {
+                    Ok(record) => record,
+                    Err(_) => {
+                        let (sanitized, replacements) = LossyString::sanitize(line);
+                        total_replacements += replacements;
+                        serde_json::from_str(&sanitized).map_err(|e| TreasuryError::Parse(e.to_string()))?
+                    }
+                };
+                Ok(Record::Json(record))
+            })
+            .collect();
+
+        Ok((records, total_replacements))
+    }
+}
+
+/// Reads CSV input, mapping columns onto named fields on [`CsvRecord`]
+struct CsvReader;
+
+impl InputReader for CsvReader {
+    fn read(&self, data: &str) -> Result<(Vec<Result<Record>>, usize)> {
+        let mut rdr = csv::Reader::from_reader(data.as_bytes());
+        let records = rdr
+            .deserialize()
+            .map(|row| row.map(Record::Csv).map_err(|e| TreasuryError::Parse(e.to_string())))
+            .collect();
+        Ok((records, 0))
+    }
+}
+
+/// Build the `InputReader` corresponding to a selected `InputFormat`
+fn reader_for(format: InputFormat) -> Box<dyn InputReader> {
+    match format {
+        InputFormat::Plain => Box::new(PlainReader),
+        InputFormat::Ndjson => Box::new(NdjsonReader),
+        InputFormat::Csv => Box::new(CsvReader),
+    }
+}
+
+/// Current format version for compiled artifacts, bumped on incompatible layout changes
+const COMPILED_FORMAT_VERSION: u32 = 1;
+
+/// A compiled, replayable snapshot of a processing run
+///
+/// Produced by the `compile` subcommand and consumed by `run` so that large
+/// input sets can be processed once and replayed cheaply afterwards.
+#[derive(Debug, Serialize, Deserialize)]
+struct CompiledArtifact {
+    /// Format version this artifact was written with
+    version: u32,
+    /// Accumulated process results
+    results: Vec<ProcessResult>,
+    /// Processor statistics captured at compile time
+    stats: serde_json::Value,
+}
 
 /// Process result structure, containing success, message, and optional data
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,43 +394,59 @@ pub struct ProcessResult {
 /// TreasuryManager processor, handling data processing and statistics
 #[derive(Debug)]
 pub struct TreasuryManagerProcessor {
-    /// Whether to enable verbose logging
-    verbose: bool,
+    /// Resolved logging verbosity
+    level: log::LevelFilter,
     /// Count of processed items
     processed_count: usize,
+    /// Count of lone-surrogate replacements made while reading this processor's input
+    surrogate_replacements: usize,
 }
 
 impl TreasuryManagerProcessor {
-    /// Create a new processor instance with the specified verbosity level
-    pub fn new(verbose: bool) -> Self {
+    /// Create a new processor instance with the specified logging verbosity
+    pub fn new(level: log::LevelFilter) -> Self {
         Self {
-            verbose,
+            level,
             processed_count: 0,
+            surrogate_replacements: 0,
         }
     }
 
-    /// Process a given string of data
+    /// Process a single input record
     ///
     /// # Arguments
     ///
-    /// * `data` - The string of data to process
+    /// * `record` - The record to process
     ///
     /// # Returns
     ///
     /// A `Result` containing the process result
-    pub fn process(&mut self, data: &str) -> Result<ProcessResult> {
-        if self.verbose {
-            debug!("Processing data of length: {}", data.len());
+    pub fn process(&mut self, record: &Record) -> Result<ProcessResult> {
+        let length = match record {
+            Record::Line(s) => s.len(),
+            Record::Json(r) => r.account.len() + r.amount.len() + r.date.len() + r.description.0.len(),
+            Record::Csv(r) => r.account.len() + r.amount.len() + r.date.len() + r.description.0.len(),
+        };
+
+        // An empty `Line` is a harmless blank line in plaintext input and
+        // should process like any other record; an all-empty structured
+        // record, though, has no treasury fields worth recording.
+        if length == 0 && !matches!(record, Record::Line(_)) {
+            return Err(TreasuryError::Parse("cannot process an empty record".to_string()));
+        }
+
+        if self.level >= log::LevelFilter::Debug {
+            debug!("Processing record of length: {}", length);
         }
 
         // Simulate processing
         self.processed_count += 1;
-        
+
         let result = ProcessResult {
             success: true,
             message: format!("Successfully processed item #{}", self.processed_count),
             data: Some(serde_json::json!({
-                "length": data.len(),
+                "length": length,
                 "processed_at": chrono::Utc::now().to_rfc3339(),
                 "item_number": self.processed_count
             })),
@@ -78,80 +463,497 @@ impl TreasuryManagerProcessor {
     pub fn get_stats(&self) -> serde_json::Value {
         serde_json::json!({
             "processed_count": self.processed_count,
-            "verbose": self.verbose
+            "level": self.level.to_string(),
+            "surrogate_replacements": self.surrogate_replacements
         })
     }
 }
 
-/// Main processing function
-pub fn run(verbose: bool, input: Option<String>, output: Option<String>) -> Result<()> {
-    // Initialize logging based on verbosity
-    if verbose {
-        env_logger::Builder::from_default_env()
-            .filter_level(log::LevelFilter::Debug)
-            .init();
-    } else {
-        env_logger::init();
+/// Main processing function, dispatching on the selected mode
+pub fn run(mode: Mode) -> Result<()> {
+    match mode {
+        Mode::Compile { level, log_file, input, output, format } => {
+            init_logging(level, log_file)?;
+            run_compile(level, input, output, format)
+        }
+        Mode::Run { level, log_file, input, output, format } => {
+            init_logging(level, log_file)?;
+            run_run(level, input, output, format)
+        }
     }
-    
+}
+
+/// Run the `compile` subcommand: process input once and persist the results
+/// plus processor state as a versioned compiled artifact
+fn run_compile(level: log::LevelFilter, input: Option<String>, output: String, format: InputFormat) -> Result<()> {
+    info!("Compiling TreasuryManager input");
+
+    let mut processor = TreasuryManagerProcessor::new(level);
+
+    let input_data = read_input(input)?;
+    let results = process_input_data(&mut processor, input_data, format)?;
+    let stats = processor.get_stats();
+
+    let artifact = CompiledArtifact {
+        version: COMPILED_FORMAT_VERSION,
+        results,
+        stats,
+    };
+
+    info!("Writing compiled artifact to: {}", output);
+    let json = serde_json::to_string_pretty(&artifact)?;
+    fs::write(output, json)?;
+
+    Ok(())
+}
+
+/// Run the `run` subcommand: accept either plaintext or a compiled artifact,
+/// skipping reprocessing when the input is already compiled
+fn run_run(level: log::LevelFilter, input: Option<String>, output: Option<String>, format: InputFormat) -> Result<()> {
     info!("Starting TreasuryManager processing");
-    
-    let mut processor = TreasuryManagerProcessor::new(verbose);
-    
-    // Read input
-    let input_data = match input {
+
+    let results = match &input {
         Some(path) => {
-            info!("Reading input from file: {}", path);
-            fs::read_to_string(path)
+            let raw = fs::read_to_string(path)?;
+            match serde_json::from_str::<CompiledArtifact>(&raw) {
+                Ok(artifact) if artifact.version == COMPILED_FORMAT_VERSION => {
+                    info!("Loaded compiled artifact from: {}", path);
+                    artifact.results
+                }
+                // A recognizable but mismatched-version artifact is a distinct
+                // failure from "this isn't a compiled artifact at all" below:
+                // its JSON would otherwise be fed right back through
+                // `process_input_data` as plaintext, silently producing
+                // garbage results instead of surfacing the real problem.
+                Ok(artifact) => {
+                    return Err(TreasuryError::Parse(format!(
+                        "compiled artifact at {} has version {}, expected {}",
+                        path, artifact.version, COMPILED_FORMAT_VERSION
+                    )));
+                }
+                Err(_) => {
+                    info!("Reading plaintext input from file: {}", path);
+                    let mut processor = TreasuryManagerProcessor::new(level);
+                    process_input_data(&mut processor, raw, format)?
+                }
+            }
         }
         None => {
             info!("No input file specified");
-            Ok(String::new())
+            let mut processor = TreasuryManagerProcessor::new(level);
+            process_input_data(&mut processor, String::new(), format)?
         }
-    }?;
-
-    // Process input data
-    let results = process_input_data(&mut processor, input_data);
+    };
 
     // Output results
-    output_results(output, results);
+    output_results(output, results)
+}
+
+/// Byte-capacity a log file is allowed to grow to before it is rotated
+const DEFAULT_LOG_ROTATE_BYTES: u64 = 10 * 1024 * 1024;
+
+/// A size-capped rotating file writer
+///
+/// When the next write would push the file past `max_bytes`, the current
+/// file is rotated to `<path>.1` (overwriting any previous rotation) before
+/// writing continues.
+struct RotatingFileWriter {
+    path: std::path::PathBuf,
+    max_bytes: u64,
+    written: u64,
+    file: fs::File,
+}
+
+impl RotatingFileWriter {
+    fn new(path: impl Into<std::path::PathBuf>, max_bytes: u64) -> Result<Self> {
+        let path = path.into();
+        let file = fs::OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self { path, max_bytes, written, file })
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        let rotated_path = format!("{}.1", self.path.display());
+        fs::rename(&self.path, rotated_path)?;
+        self.file = fs::OpenOptions::new().create(true).write(true).truncate(true).open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl std::io::Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Initialize logging based on the resolved verbosity, optionally installing
+/// a second dispatch that writes timestamped lines to a rotating log file
+fn init_logging(level: log::LevelFilter, log_file: Option<String>) -> Result<()> {
+    let mut dispatch = fern::Dispatch::new()
+        .level(level)
+        .chain(std::io::stderr());
 
+    if let Some(path) = log_file {
+        let writer = RotatingFileWriter::new(path, DEFAULT_LOG_ROTATE_BYTES)?;
+        dispatch = dispatch.chain(
+            fern::Dispatch::new()
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "[{} {} {}] {}",
+                        chrono::Utc::now().to_rfc3339(),
+                        record.level(),
+                        record.target(),
+                        message
+                    ))
+                })
+                .chain(Box::new(writer) as Box<dyn std::io::Write + Send>),
+        );
+    }
+
+    dispatch.apply()?;
     Ok(())
 }
 
-/// Process a string of input data
+/// Read input data from an optional file path, returning an empty string when none is given
+fn read_input(input: Option<String>) -> Result<String> {
+    match input {
+        Some(path) => {
+            info!("Reading input from file: {}", path);
+            Ok(fs::read_to_string(path)?)
+        }
+        None => {
+            info!("No input file specified");
+            Ok(String::new())
+        }
+    }
+}
+
+/// Process a string of input data, parsed according to the given format
+///
+/// Neither a record that fails to parse nor one that fails once parsed (e.g.
+/// a structured record whose fields are all empty) aborts the batch: either
+/// failure is collected into the
+/// returned results as a `success: false` entry, and processing continues.
 ///
 /// # Arguments
 ///
 /// * `processor` - The processor instance
 /// * `input_data` - The string of input data
+/// * `format` - The encoding the input data is in
 ///
 /// # Returns
 ///
-/// A vector of process results
-fn process_input_data(processor: &mut TreasuryManagerProcessor, input_data: String) -> Vec<ProcessResult> {
+/// A `Result` containing the vector of process results
+fn process_input_data(processor: &mut TreasuryManagerProcessor, input_data: String, format: InputFormat) -> Result<Vec<ProcessResult>> {
     let mut results = Vec::new();
 
-    for line in input_data.lines() {
-        let result = processor.process(line)?;
-        results.push(result);
+    let (records, replacements) = reader_for(format).read(&input_data)?;
+    processor.surrogate_replacements += replacements;
+    for record in records {
+        match record.and_then(|r| processor.process(&r)) {
+            Ok(result) => results.push(result),
+            Err(e) => results.push(ProcessResult {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            }),
+        }
     }
 
-    results
+    Ok(results)
 }
 
-/// Output process results to a file
+/// Output process results, either as pretty JSON to a file or, when no
+/// output path is given, as a human-readable table on stdout
 ///
 /// # Arguments
 ///
 /// * `output` - The output file path
 /// * `results` - The vector of process results
-fn output_results(output: Option<String>, results: Vec<ProcessResult>) {
+fn output_results(output: Option<String>, results: Vec<ProcessResult>) -> Result<()> {
     if let Some(path) = output {
         info!("Outputting results to file: {}", path);
         let json = serde_json::to_string_pretty(&results)?;
-        fs::write(path, json).expect("Failed to write output file");
+        fs::write(path, json)?;
     } else {
-        info!("No output file specified");
+        print!("{}", render_table(&results));
+    }
+
+    Ok(())
+}
+
+/// Render process results as an aligned table: item number, success,
+/// message, and data length
+fn render_table(results: &[ProcessResult]) -> String {
+    let headers = ["#", "success", "message", "data length"];
+
+    let rows: Vec<[String; 4]> = results
+        .iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let data_len = r.data.as_ref().map(|d| d.to_string().len()).unwrap_or(0);
+            [
+                (i + 1).to_string(),
+                r.success.to_string(),
+                r.message.clone(),
+                data_len.to_string(),
+            ]
+        })
+        .collect();
+
+    let mut widths: [usize; 4] = headers.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let mut out = String::new();
+    let header_cells: Vec<String> = headers
+        .iter()
+        .zip(&widths)
+        .map(|(h, width)| format!("{:<width$}", h, width = width))
+        .collect();
+    out.push_str(&header_cells.join("  "));
+    out.push('\n');
+
+    for row in &rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        out.push_str(&cells.join("  "));
+        out.push('\n');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A process-unique scratch file path under the OS temp directory, so
+    /// parallel `cargo test` runs don't collide on the same file
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("treasurymanager_test_{}_{}", std::process::id(), name))
+    }
+
+    #[test]
+    fn compile_then_run_round_trip_skips_reprocessing() {
+        let input_path = temp_path("round_trip_input.txt");
+        let artifact_path = temp_path("round_trip_artifact.json");
+        let output_path = temp_path("round_trip_output.json");
+        fs::write(&input_path, "hello\n").unwrap();
+
+        run_compile(log::LevelFilter::Off, Some(input_path.to_str().unwrap().to_string()), artifact_path.to_str().unwrap().to_string(), InputFormat::Plain).unwrap();
+
+        run_run(
+            log::LevelFilter::Off,
+            Some(artifact_path.to_str().unwrap().to_string()),
+            Some(output_path.to_str().unwrap().to_string()),
+            InputFormat::Plain,
+        )
+        .unwrap();
+
+        let output_json = fs::read_to_string(&output_path).unwrap();
+        let results: Vec<ProcessResult> = serde_json::from_str(&output_json).unwrap();
+        // The one-line input compiles to exactly one result; if `run` had
+        // reprocessed the artifact's pretty-printed JSON as plaintext
+        // instead of reusing it, this would instead hold one result per
+        // line of that JSON.
+        assert_eq!(results.len(), 1);
+        assert!(results[0].success);
+
+        fs::remove_file(&input_path).ok();
+        fs::remove_file(&artifact_path).ok();
+        fs::remove_file(&output_path).ok();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn run_rejects_artifact_with_mismatched_version() {
+        let artifact_path = temp_path("version_mismatch_artifact.json");
+        let artifact = CompiledArtifact {
+            version: COMPILED_FORMAT_VERSION + 1,
+            results: Vec::new(),
+            stats: serde_json::json!({}),
+        };
+        fs::write(&artifact_path, serde_json::to_string_pretty(&artifact).unwrap()).unwrap();
+
+        let err = run_run(log::LevelFilter::Off, Some(artifact_path.to_str().unwrap().to_string()), None, InputFormat::Plain).unwrap_err();
+        assert!(matches!(err, TreasuryError::Parse(_)));
+        assert!(err.to_string().contains("version"));
+
+        fs::remove_file(&artifact_path).ok();
+    }
+
+    #[test]
+    fn run_falls_back_to_plaintext_when_input_is_not_an_artifact() {
+        let input_path = temp_path("plaintext_fallback_input.txt");
+        let output_path = temp_path("plaintext_fallback_output.json");
+        fs::write(&input_path, "hello\nworld\n").unwrap();
+
+        run_run(
+            log::LevelFilter::Off,
+            Some(input_path.to_str().unwrap().to_string()),
+            Some(output_path.to_str().unwrap().to_string()),
+            InputFormat::Plain,
+        )
+        .unwrap();
+
+        let output_json = fs::read_to_string(&output_path).unwrap();
+        let results: Vec<ProcessResult> = serde_json::from_str(&output_json).unwrap();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|r| r.success));
+
+        fs::remove_file(&input_path).ok();
+        fs::remove_file(&output_path).ok();
+    }
+
+    #[test]
+    fn sanitize_combines_valid_surrogate_pair() {
+        let (out, replacements) = LossyString::sanitize("A\\uD83D\\uDE00B");
+        assert_eq!(out, "A\u{1F600}B");
+        assert_eq!(replacements, 0);
+    }
+
+    #[test]
+    fn sanitize_replaces_lone_high_surrogate() {
+        let (out, replacements) = LossyString::sanitize("A\\uD800B");
+        assert_eq!(out, "A\u{FFFD}B");
+        assert_eq!(replacements, 1);
+    }
+
+    #[test]
+    fn sanitize_replaces_lone_low_surrogate() {
+        let (out, replacements) = LossyString::sanitize("A\\uDC00B");
+        assert_eq!(out, "A\u{FFFD}B");
+        assert_eq!(replacements, 1);
+    }
+
+    #[test]
+    fn sanitize_leaves_non_surrogate_escape_verbatim() {
+        let (out, replacements) = LossyString::sanitize("A\\u0022B");
+        assert_eq!(out, "A\\u0022B");
+        assert_eq!(replacements, 0);
+    }
+
+    #[test]
+    fn sanitize_does_not_mistake_escaped_backslash_for_unicode_escape() {
+        let (out, replacements) = LossyString::sanitize("A\\\\uD800B");
+        assert_eq!(out, "A\\\\uD800B");
+        assert_eq!(replacements, 0);
+    }
+
+    #[test]
+    fn plain_reader_turns_every_line_into_a_record() {
+        let (records, replacements) = PlainReader.read("first\nsecond\n").unwrap();
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.is_ok()));
+        assert_eq!(replacements, 0);
+    }
+
+    #[test]
+    fn ndjson_reader_keeps_good_records_when_a_line_fails_to_parse() {
+        let good = r#"{"account":"a","amount":"1","date":"2024-01-01","description":"ok"}"#;
+        let data = format!("{}\nnot json at all\n{}\n", good, good);
+
+        let (records, _) = NdjsonReader.read(&data).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+        assert!(records[2].is_ok());
+    }
+
+    #[test]
+    fn ndjson_reader_skips_blank_lines() {
+        let good = r#"{"account":"a","amount":"1","date":"2024-01-01","description":"ok"}"#;
+        let data = format!("{}\n\n{}\n", good, good);
+
+        let (records, _) = NdjsonReader.read(&data).unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert!(records.iter().all(|r| r.is_ok()));
+    }
+
+    #[test]
+    fn csv_reader_keeps_good_rows_when_a_row_fails_to_parse() {
+        let data = "account,amount,date,description\n\
+                     acct-1,100,2024-01-01,first\n\
+                     acct-2,200\n\
+                     acct-3,300,2024-01-03,third\n";
+
+        let (records, _) = CsvReader.read(data).unwrap();
+
+        assert_eq!(records.len(), 3);
+        assert!(records[0].is_ok());
+        assert!(records[1].is_err());
+        assert!(records[2].is_ok());
+    }
+
+    #[test]
+    fn configuration_load_falls_back_to_defaults_without_a_file() {
+        let config = Configuration::load(Some(temp_path("nonexistent_config.toml").to_str().unwrap())).unwrap();
+        assert_eq!(config.level, None);
+        assert_eq!(config.format, None);
+        assert_eq!(config.output, None);
+    }
+
+    #[test]
+    fn configuration_load_reads_values_from_an_explicit_path() {
+        let config_path = temp_path("config.toml");
+        fs::write(&config_path, "level = \"debug\"\nformat = \"csv\"\noutput = \"out.json\"\n").unwrap();
+
+        let config = Configuration::load(Some(config_path.to_str().unwrap())).unwrap();
+
+        assert_eq!(config.level.as_deref(), Some("debug"));
+        assert_eq!(config.format, Some(InputFormat::Csv));
+        assert_eq!(config.output.as_deref(), Some("out.json"));
+
+        fs::remove_file(&config_path).ok();
+    }
+
+    #[test]
+    fn render_table_aligns_columns_to_the_widest_cell() {
+        let results = vec![
+            ProcessResult { success: true, message: "ok".to_string(), data: None },
+            ProcessResult { success: false, message: "a much longer failure message".to_string(), data: Some(serde_json::json!({"length": 5})) },
+        ];
+
+        let table = render_table(&results);
+        let lines: Vec<&str> = table.lines().collect();
+
+        assert_eq!(lines.len(), 3);
+        // Every row's "message" column starts at the same offset, proving
+        // the narrower rows were padded out to the widest cell in the table.
+        let message_offset = lines[0].find("message").unwrap();
+        assert_eq!(lines[1].find("ok").unwrap(), message_offset);
+        assert_eq!(lines[2].find("a much longer failure message").unwrap(), message_offset);
+    }
+
+    #[test]
+    fn run_surfaces_an_unwritable_output_path_as_an_error() {
+        let mode = Mode::Run {
+            level: log::LevelFilter::Off,
+            log_file: None,
+            input: None,
+            output: Some("/nonexistent-dir-for-treasurymanager-tests/out.json".to_string()),
+            format: InputFormat::Plain,
+        };
+
+        let err = run(mode).unwrap_err();
+        assert!(matches!(err, TreasuryError::Io(_)));
+    }
+}
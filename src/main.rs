@@ -3,30 +3,232 @@
  * Main executable for TreasuryManager
  */
 
-use clap::Parser;
-use treasurymanager::{Result, run};
+use clap::{Parser, Subcommand, ValueEnum};
+use treasurymanager::{Configuration, InputFormat, Mode, Result, TreasuryError, run};
 
 /// Command-line arguments parser
 #[derive(Parser)]
 #[command(version, about = "TreasuryManager - A Rust implementation")]
 struct Cli {
-    /// Enable verbose output
-    #[arg(short, long)]
-    verbose: bool,
-    
-    /// Path to input file
-    #[arg(short = 'i', long = "input")]
-    input: Option<String>,
-    
-    /// Path to output file
-    #[arg(short = 'o', long = "output")]
-    output: Option<String>,
+    /// Path to a TOML configuration file (defaults to the XDG config directory)
+    #[arg(long = "config", global = true)]
+    config: Option<String>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Available subcommands
+#[derive(Subcommand)]
+enum Commands {
+    /// Process treasury input once and persist the results as a compiled artifact
+    Compile {
+        #[command(flatten)]
+        verbosity: Verbosity,
+
+        /// Path to input file
+        #[arg(short = 'i', long = "input")]
+        input: Option<String>,
+
+        /// Path to write the compiled artifact (falls back to the config file's `output`)
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+
+        /// Format of the input file (falls back to the config file's `format`, then plain)
+        #[arg(short = 'f', long = "format", value_enum)]
+        format: Option<Format>,
+    },
+    /// Run treasury processing, accepting plaintext or a previously compiled artifact
+    Run {
+        #[command(flatten)]
+        verbosity: Verbosity,
+
+        /// Path to input file (plaintext or a compiled artifact)
+        #[arg(short = 'i', long = "input")]
+        input: Option<String>,
+
+        /// Path to output file; prints a table to stdout when omitted
+        #[arg(short = 'o', long = "output")]
+        output: Option<String>,
+
+        /// Format of the input file (falls back to the config file's `format`, then plain)
+        #[arg(short = 'f', long = "format", value_enum)]
+        format: Option<Format>,
+    },
+}
+
+/// Logging verbosity flags shared by every subcommand
+#[derive(clap::Args)]
+struct Verbosity {
+    /// Increase logging verbosity (Info -> Debug -> Trace); repeatable
+    #[arg(short = 'v', long = "verbose", action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+
+    /// Decrease logging verbosity (Info -> Warn -> Error); repeatable
+    #[arg(short = 'q', long = "quiet", action = clap::ArgAction::Count, global = true)]
+    quiet: u8,
+
+    /// Write logs to this file in addition to stderr, rotating it once it grows too large
+    #[arg(long = "log-file", global = true)]
+    log_file: Option<String>,
+}
+
+impl Verbosity {
+    /// Resolve the `-v`/`-q` counts into a concrete log level filter, relative to `base`
+    fn level(&self, base: log::LevelFilter) -> log::LevelFilter {
+        const LEVELS: [log::LevelFilter; 6] = [
+            log::LevelFilter::Off,
+            log::LevelFilter::Error,
+            log::LevelFilter::Warn,
+            log::LevelFilter::Info,
+            log::LevelFilter::Debug,
+            log::LevelFilter::Trace,
+        ];
+        let base_index = LEVELS.iter().position(|l| *l == base).unwrap_or(3) as i32;
+        let index = (base_index + self.verbose as i32 - self.quiet as i32)
+            .clamp(0, LEVELS.len() as i32 - 1);
+        LEVELS[index as usize]
+    }
+}
+
+/// Input encoding accepted by the `-f`/`--format` flag
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    /// One opaque record per line
+    Plain,
+    /// Newline-delimited JSON, one value per line
+    Ndjson,
+    /// Comma-separated values mapped onto named treasury fields
+    Csv,
+}
+
+impl From<Format> for InputFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Plain => InputFormat::Plain,
+            Format::Ndjson => InputFormat::Ndjson,
+            Format::Csv => InputFormat::Csv,
+        }
+    }
 }
 
 fn main() -> Result<()> {
     // Parse command-line arguments
     let args = Cli::parse();
-    
-    // Run the application with parsed arguments
-    run(args.verbose, args.input, args.output)
-}
\ No newline at end of file
+
+    // Load file-based defaults, which CLI flags take precedence over
+    let config = Configuration::load(args.config.as_deref())?;
+    let base_level = config
+        .level
+        .as_deref()
+        .and_then(|s| s.parse::<log::LevelFilter>().ok())
+        .unwrap_or(log::LevelFilter::Info);
+
+    // Map the parsed subcommand onto the library's processing mode
+    let mode = build_mode(args.command, &config, base_level)?;
+
+    // Run the application with the selected mode
+    run(mode)
+}
+
+/// Map a parsed subcommand onto the library's processing mode, resolving
+/// verbosity relative to `base_level` and falling back to `config` for any
+/// flag the user didn't pass on the command line
+fn build_mode(command: Commands, config: &Configuration, base_level: log::LevelFilter) -> Result<Mode> {
+    Ok(match command {
+        Commands::Compile { verbosity, input, output, format } => Mode::Compile {
+            level: verbosity.level(base_level),
+            log_file: verbosity.log_file,
+            input,
+            output: output
+                .or_else(|| config.output.clone())
+                .ok_or_else(|| TreasuryError::Parse("missing required output path (pass --output or set it in the config file)".into()))?,
+            format: format.map(InputFormat::from).or(config.format).unwrap_or_default(),
+        },
+        Commands::Run { verbosity, input, output, format } => Mode::Run {
+            level: verbosity.level(base_level),
+            log_file: verbosity.log_file,
+            input,
+            output: output.or_else(|| config.output.clone()),
+            format: format.map(InputFormat::from).or(config.format).unwrap_or_default(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn verbosity(verbose: u8, quiet: u8) -> Verbosity {
+        Verbosity { verbose, quiet, log_file: None }
+    }
+
+    #[test]
+    fn level_defaults_to_base_with_no_flags() {
+        assert_eq!(verbosity(0, 0).level(log::LevelFilter::Info), log::LevelFilter::Info);
+        assert_eq!(verbosity(0, 0).level(log::LevelFilter::Warn), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn verbose_increases_the_level_from_each_base() {
+        assert_eq!(verbosity(1, 0).level(log::LevelFilter::Info), log::LevelFilter::Debug);
+        assert_eq!(verbosity(2, 0).level(log::LevelFilter::Info), log::LevelFilter::Trace);
+        assert_eq!(verbosity(1, 0).level(log::LevelFilter::Error), log::LevelFilter::Warn);
+    }
+
+    #[test]
+    fn quiet_decreases_the_level_from_each_base() {
+        assert_eq!(verbosity(0, 1).level(log::LevelFilter::Info), log::LevelFilter::Warn);
+        assert_eq!(verbosity(0, 2).level(log::LevelFilter::Info), log::LevelFilter::Error);
+    }
+
+    #[test]
+    fn verbose_and_quiet_cancel_each_other_out() {
+        assert_eq!(verbosity(2, 2).level(log::LevelFilter::Info), log::LevelFilter::Info);
+    }
+
+    #[test]
+    fn level_saturates_at_trace_instead_of_wrapping() {
+        assert_eq!(verbosity(10, 0).level(log::LevelFilter::Trace), log::LevelFilter::Trace);
+        assert_eq!(verbosity(10, 0).level(log::LevelFilter::Info), log::LevelFilter::Trace);
+    }
+
+    #[test]
+    fn level_saturates_at_off_instead_of_wrapping() {
+        assert_eq!(verbosity(0, 10).level(log::LevelFilter::Off), log::LevelFilter::Off);
+        assert_eq!(verbosity(0, 10).level(log::LevelFilter::Info), log::LevelFilter::Off);
+    }
+
+    fn run_command(output: Option<&str>, format: Option<Format>) -> Commands {
+        Commands::Run {
+            verbosity: verbosity(0, 0),
+            input: None,
+            output: output.map(str::to_string),
+            format,
+        }
+    }
+
+    #[test]
+    fn cli_output_flag_overrides_config() {
+        let config = Configuration { output: Some("from_config.json".to_string()), ..Configuration::default() };
+        let mode = build_mode(run_command(Some("from_cli.json"), None), &config, log::LevelFilter::Info).unwrap();
+        let Mode::Run { output, .. } = mode else { panic!("expected Mode::Run") };
+        assert_eq!(output, Some("from_cli.json".to_string()));
+    }
+
+    #[test]
+    fn config_output_is_used_when_cli_flag_is_absent() {
+        let config = Configuration { output: Some("from_config.json".to_string()), ..Configuration::default() };
+        let mode = build_mode(run_command(None, None), &config, log::LevelFilter::Info).unwrap();
+        let Mode::Run { output, .. } = mode else { panic!("expected Mode::Run") };
+        assert_eq!(output, Some("from_config.json".to_string()));
+    }
+
+    #[test]
+    fn cli_format_flag_overrides_config() {
+        let config = Configuration { format: Some(InputFormat::Csv), ..Configuration::default() };
+        let mode = build_mode(run_command(None, Some(Format::Ndjson)), &config, log::LevelFilter::Info).unwrap();
+        let Mode::Run { format, .. } = mode else { panic!("expected Mode::Run") };
+        assert_eq!(format, InputFormat::Ndjson);
+    }
+}